@@ -0,0 +1,363 @@
+//! Narrow abstraction over the `wgpu` calls the benchmark loops in `main`
+//! need: instance/adapter/device setup, buffer creation, mapping, and
+//! timestamp-query handling. `WgpuBackend` is the only implementation today,
+//! but the benchmark loops only depend on the `GpuBackend` trait, so a second
+//! implementation (another WebGPU driver, or a mock for CI without a GPU)
+//! doesn't require touching `run_copy`/`execute_gpu`.
+//!
+//! Known gap: the compute-shader dispatch path in `main` (shader modules,
+//! bind groups, its own timestamp/pipeline-statistics query sets) is
+//! inherently `wgpu`-specific and still goes through `device()`/`queue()`
+//! rather than `GpuBackend`, so it isn't covered by the same swap-the-driver
+//! story as the copy path. Folding it into this trait (or a sibling
+//! `ComputeBackend`) is a follow-up, not done here.
+
+use std::time::Duration;
+
+use futures::FutureExt;
+
+/// Feature support actually advertised by an adapter, used to decide which
+/// optional measurements (GPU timestamps, pipeline statistics) to enable.
+#[derive(Debug, Clone, Copy)]
+pub struct GpuFeatureSupport {
+    pub timestamps: bool,
+    pub pipeline_statistics: bool,
+}
+
+/// Narrow interface the benchmark loops drive a GPU through.
+pub trait GpuBackend {
+    type Buffer;
+
+    /// Adapter name, as reported by `wgpu::AdapterInfo`.
+    fn name(&self) -> &str;
+
+    /// Human-readable `"name (backend, device type)"` line for banners.
+    fn description(&self) -> String;
+
+    /// Feature support advertised by the adapter this backend was created from.
+    fn support(&self) -> GpuFeatureSupport;
+
+    /// Nanoseconds per GPU timestamp tick, for scaling raw timestamp deltas.
+    fn timestamp_scale(&self) -> f32;
+
+    /// Creates a host-writable, device-readable buffer of `size` bytes.
+    fn create_upload_buffer(&self, size: u64) -> Self::Buffer;
+
+    /// Creates a device-writable, host-readable buffer of `size` bytes.
+    fn create_download_buffer(&self, size: u64) -> Self::Buffer;
+
+    /// Maps `buffer` for writing and copies `data` into it. Returns the
+    /// elapsed map+copy time, or `None` if the map stalled past `timeout`.
+    async fn write(&self, buffer: &Self::Buffer, data: &[u8], timeout: Duration) -> Option<Duration>;
+
+    /// Copies `size` bytes from `src` to `dst`. Timed with a GPU timestamp
+    /// query when the adapter supports it (`Some`); otherwise the copy still
+    /// runs but is left unmeasured (`None`).
+    async fn copy(
+        &self,
+        src: &Self::Buffer,
+        dst: &Self::Buffer,
+        size: u64,
+        timeout: Duration,
+    ) -> Option<Duration>;
+
+    /// Maps `buffer` for reading and copies its contents into `out`. Returns
+    /// the elapsed map+copy time, or `None` if the map stalled past `timeout`.
+    async fn read_back(&self, buffer: &Self::Buffer, out: &mut [u8], timeout: Duration) -> Option<Duration>;
+
+    /// Raw `wgpu` handles, for the compute-shader dispatch path that this
+    /// trait doesn't abstract.
+    fn device(&self) -> &wgpu::Device;
+    fn queue(&self) -> &wgpu::Queue;
+}
+
+/// `GpuBackend` implementation backed by the real `wgpu` crate.
+pub struct WgpuBackend {
+    name: String,
+    backend_type: wgpu::Backend,
+    device_type: wgpu::DeviceType,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    support: GpuFeatureSupport,
+    timestamp_period: f32,
+}
+
+/// Raw contents of a resolved 2-entry `Timestamp` query set: a start and end
+/// tick, scaled by `timestamp_scale()` into a `Duration`. Shared with
+/// `main`'s compute-dispatch path, which resolves its own timestamp query
+/// set directly (see the module doc comment above).
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct TimestampData {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl WgpuBackend {
+    /// Enumerates every adapter matching `backends`/`adapter_name_filter`,
+    /// sorted by `power_preference`, and requests a device from each one.
+    /// Only asks for `TIMESTAMP_QUERY`/`PIPELINE_STATISTICS_QUERY` when the
+    /// adapter actually advertises them, so the request never fails on an
+    /// adapter that merely lacks optional query support.
+    pub async fn enumerate(
+        backends: wgpu::Backends,
+        adapter_name_filter: Option<&str>,
+        power_preference: wgpu::PowerPreference,
+    ) -> Vec<WgpuBackend> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends,
+            dx12_shader_compiler: wgpu::Dx12Compiler::Fxc,
+        });
+
+        let mut adapters: Vec<wgpu::Adapter> = instance.enumerate_adapters(backends).collect();
+        if let Some(filter) = adapter_name_filter {
+            let filter = filter.to_lowercase();
+            adapters.retain(|adapter| adapter.get_info().name.to_lowercase().contains(&filter));
+        }
+        sort_adapters_by_power_preference(&mut adapters, power_preference);
+
+        let mut backends = Vec::with_capacity(adapters.len());
+        for adapter in adapters {
+            backends.push(WgpuBackend::from_adapter(adapter).await);
+        }
+        backends
+    }
+
+    async fn from_adapter(adapter: wgpu::Adapter) -> WgpuBackend {
+        let info = adapter.get_info();
+        let supported = adapter.features();
+        let support = GpuFeatureSupport {
+            timestamps: supported.contains(wgpu::Features::TIMESTAMP_QUERY),
+            pipeline_statistics: supported.contains(wgpu::Features::PIPELINE_STATISTICS_QUERY),
+        };
+
+        let mut features = wgpu::Features::empty();
+        if support.timestamps {
+            features |= wgpu::Features::TIMESTAMP_QUERY;
+        }
+        if support.pipeline_statistics {
+            features |= wgpu::Features::PIPELINE_STATISTICS_QUERY;
+        }
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    features,
+                    limits: wgpu::Limits::default(),
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        let timestamp_period = queue.get_timestamp_period();
+
+        WgpuBackend {
+            name: info.name,
+            backend_type: info.backend,
+            device_type: info.device_type,
+            device,
+            queue,
+            support,
+            timestamp_period,
+        }
+    }
+
+}
+
+/// Drives `device.poll(Maintain::Poll)` in a loop, checking `receiver`
+/// without blocking, until it resolves or `timeout` elapses. Unlike
+/// `device.poll(Maintain::Wait)`, this never hangs forever on a wedged
+/// adapter. Shared by `WgpuBackend`'s trait methods and by `main`'s
+/// compute-shader dispatch path, which still talks to `wgpu` directly.
+pub async fn poll_until_mapped<T>(
+    device: &wgpu::Device,
+    receiver: futures_intrusive::channel::shared::OneshotReceiver<T>,
+    timeout: Duration,
+) -> Option<T> {
+    let deadline = std::time::Instant::now() + timeout;
+    loop {
+        device.poll(wgpu::Maintain::Poll);
+        if let Some(value) = receiver.receive().now_or_never() {
+            return value;
+        }
+        if std::time::Instant::now() >= deadline {
+            return None;
+        }
+        std::thread::sleep(Duration::from_micros(100));
+    }
+}
+
+impl GpuBackend for WgpuBackend {
+    type Buffer = wgpu::Buffer;
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> String {
+        format!("{} ({:?}, {:?})", self.name, self.backend_type, self.device_type)
+    }
+
+    fn support(&self) -> GpuFeatureSupport {
+        self.support
+    }
+
+    fn timestamp_scale(&self) -> f32 {
+        self.timestamp_period
+    }
+
+    fn create_upload_buffer(&self, size: u64) -> wgpu::Buffer {
+        self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("upload buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_WRITE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_download_buffer(&self, size: u64) -> wgpu::Buffer {
+        self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("download buffer"),
+            size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        })
+    }
+
+    async fn write(&self, buffer: &wgpu::Buffer, data: &[u8], timeout: Duration) -> Option<Duration> {
+        self.device.poll(wgpu::Maintain::Poll);
+
+        let start = std::time::Instant::now();
+        let buffer_slice = buffer.slice(..);
+        let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
+        buffer_slice.map_async(wgpu::MapMode::Write, move |v| sender.send(v).unwrap());
+
+        match poll_until_mapped(&self.device, receiver, timeout).await {
+            Some(Ok(())) => {
+                let mut mapped = buffer_slice.get_mapped_range_mut();
+                mapped.copy_from_slice(data);
+                drop(mapped);
+                buffer.unmap();
+                Some(start.elapsed())
+            }
+            Some(Err(err)) => {
+                eprintln!("failed to map upload buffer: {}", err);
+                None
+            }
+            None => None,
+        }
+    }
+
+    async fn copy(
+        &self,
+        src: &wgpu::Buffer,
+        dst: &wgpu::Buffer,
+        size: u64,
+        timeout: Duration,
+    ) -> Option<Duration> {
+        if !self.support.timestamps {
+            let mut encoder = self
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+            encoder.copy_buffer_to_buffer(src, 0, dst, 0, size);
+            self.queue.submit(Some(encoder.finish()));
+            return None;
+        }
+
+        let timing_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("timing buffer"),
+            size: 2 * std::mem::size_of::<u64>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let query_set = self.device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: None,
+            count: 2,
+            ty: wgpu::QueryType::Timestamp,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.write_timestamp(&query_set, 0);
+        encoder.copy_buffer_to_buffer(src, 0, dst, 0, size);
+        encoder.write_timestamp(&query_set, 1);
+        encoder.resolve_query_set(&query_set, 0..2, &timing_buffer, 0);
+        self.queue.submit(Some(encoder.finish()));
+
+        let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
+        let _ = timing_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |v| sender.send(v).unwrap());
+
+        match poll_until_mapped(&self.device, receiver, timeout).await {
+            Some(Ok(())) => {
+                let view = timing_buffer.slice(..).get_mapped_range();
+                let data: &TimestampData = bytemuck::from_bytes(&view);
+                let elapsed_ns = (data.end - data.start) as f64 * self.timestamp_period as f64;
+                Some(Duration::from_nanos(elapsed_ns as u64))
+            }
+            Some(Err(err)) => {
+                eprintln!("failed to map GPU/GPU timing buffer: {}", err);
+                None
+            }
+            None => None,
+        }
+    }
+
+    async fn read_back(&self, buffer: &wgpu::Buffer, out: &mut [u8], timeout: Duration) -> Option<Duration> {
+        let start = std::time::Instant::now();
+        let buffer_slice = buffer.slice(..);
+        let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |v| sender.send(v).unwrap());
+
+        match poll_until_mapped(&self.device, receiver, timeout).await {
+            Some(Ok(())) => {
+                let mapped = buffer_slice.get_mapped_range();
+                out.copy_from_slice(&mapped);
+                drop(mapped);
+                buffer.unmap();
+                Some(start.elapsed())
+            }
+            Some(Err(err)) => {
+                eprintln!("failed to map download buffer: {}", err);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+}
+
+/// Resolves the power preference used to order enumerated adapters, from the
+/// `WGPU_POWER_PREF` environment variable (`low` or `high`). Defaults to
+/// `HighPerformance`, matching the previous hardcoded behaviour.
+pub fn resolve_power_preference() -> wgpu::PowerPreference {
+    match std::env::var("WGPU_POWER_PREF").ok().as_deref() {
+        Some("low") => wgpu::PowerPreference::LowPower,
+        Some("high") => wgpu::PowerPreference::HighPerformance,
+        _ => wgpu::PowerPreference::HighPerformance,
+    }
+}
+
+/// Moves adapters matching `preference` (discrete GPUs for `HighPerformance`,
+/// integrated GPUs/CPUs for `LowPower`) to the front, without dropping the rest.
+fn sort_adapters_by_power_preference(adapters: &mut [wgpu::Adapter], preference: wgpu::PowerPreference) {
+    adapters.sort_by_key(|adapter| {
+        let preferred = matches!(
+            (preference, adapter.get_info().device_type),
+            (wgpu::PowerPreference::HighPerformance, wgpu::DeviceType::DiscreteGpu)
+                | (wgpu::PowerPreference::LowPower, wgpu::DeviceType::IntegratedGpu)
+                | (wgpu::PowerPreference::LowPower, wgpu::DeviceType::Cpu)
+        );
+        !preferred
+    });
+}
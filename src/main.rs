@@ -1,4 +1,5 @@
 use std::mem;
+use std::path::PathBuf;
 use std::time::{Duration, Instant};
 use structopt::StructOpt;
 
@@ -6,11 +7,87 @@ use prettytable::{cell, format, row, Table};
 
 use pbr::ProgressBar;
 
+mod gpu;
+mod results;
+
+use gpu::{GpuBackend, TimestampData, WgpuBackend};
+use results::{MeasurementRecord, OutputFormat, ResultsWriter};
+
+/// Matches the single `COMPUTE_SHADER_INVOCATIONS` statistic resolved into a
+/// pipeline-statistics query. wgpu writes one `u64` per requested bit, in bit
+/// order, so requesting only this bit keeps the layout to one field.
 #[repr(C)]
 #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
-struct TimestampData {
-    start: u64,
-    end: u64,
+struct PipelineStatisticsData {
+    compute_shader_invocations: u64,
+}
+
+/// Result of a single compute dispatch: elapsed GPU time, when the adapter
+/// supports `TIMESTAMP_QUERY` (`None` otherwise, same as the copy-mode
+/// GPU/GPU phase), plus, when `--stats` is enabled and supported, the
+/// invocation count reported by the pipeline-statistics query.
+struct ComputeResult {
+    time: Option<Duration>,
+    invocations: Option<u64>,
+}
+
+/// The number of fused multiply-add iterations applied to every element in
+/// the compute benchmark. Two floating point ops (one multiply, one add) per
+/// iteration, so `elements * FMA_ITERATIONS * 2` gives the total flop count.
+const FMA_ITERATIONS: u32 = 256;
+
+/// Must match `@workgroup_size` in shader.wgsl.
+const WORKGROUP_SIZE: u32 = 256;
+
+/// wgpu's default `max_compute_workgroups_per_dimension` limit. The largest
+/// default sweep size dispatches more workgroups than fit in a single
+/// dimension, so `workgroup_grid` spreads the remainder into Y.
+const MAX_WORKGROUPS_PER_DIMENSION: u32 = 65_535;
+
+const COMPUTE_SHADER: &str = include_str!("shader.wgsl");
+
+/// Splits `total` workgroups into an `(x, y)` dispatch grid, each axis no
+/// larger than `MAX_WORKGROUPS_PER_DIMENSION`. Mirrors the flat-index
+/// reconstruction in shader.wgsl's `main`.
+fn workgroup_grid(total: u32) -> (u32, u32) {
+    if total <= MAX_WORKGROUPS_PER_DIMENSION {
+        (total, 1)
+    } else {
+        let x = MAX_WORKGROUPS_PER_DIMENSION;
+        let y = (total + x - 1) / x;
+        (x, y)
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ComputeParams {
+    iterations: u32,
+    _padding: [u32; 3],
+}
+
+/// Which benchmark subsystem to exercise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Memory-copy bandwidth via `copy_buffer_to_buffer` (upload/GPU-GPU/download).
+    Copy,
+    /// Compute dispatch throughput via a fused multiply-add compute shader.
+    Compute,
+}
+
+impl std::str::FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "copy" => Ok(Mode::Copy),
+            "compute" => Ok(Mode::Compute),
+            other => Err(format!(
+                "unknown mode `{}`, expected `copy` or `compute`",
+                other
+            )),
+        }
+    }
 }
 
 /// Configuration struct gpu benchmarking
@@ -28,6 +105,79 @@ struct Configuration {
     /// Whether to verify the data of the copy. Can take a long time.
     #[structopt(long, short = "v")]
     verify: bool,
+
+    /// Which benchmark subsystem to run: `copy` (memory bandwidth) or `compute` (FMA throughput)
+    #[structopt(long, default_value = "copy")]
+    mode: Mode,
+
+    /// Which backend(s) to enumerate adapters from
+    #[structopt(long, default_value = "all")]
+    backend: BackendArg,
+
+    /// Only run adapters whose name contains this substring (case-insensitive).
+    /// Falls back to the WGPU_ADAPTER_NAME environment variable when absent.
+    #[structopt(long)]
+    adapter: Option<String>,
+
+    /// How long to wait for a GPU map/submit to complete before treating it as
+    /// stalled, in milliseconds. Prevents a wedged adapter from hanging forever.
+    #[structopt(long, default_value = "5000")]
+    timeout_ms: u64,
+
+    /// Collect pipeline-statistics counters (compute-shader invocations) alongside
+    /// compute-mode timings. Ignored if the adapter doesn't support the feature.
+    #[structopt(long)]
+    stats: bool,
+
+    /// Write structured per-measurement records to this path, in addition to
+    /// the live progress bar and human-readable tables. See `--format`.
+    #[structopt(long)]
+    output: Option<PathBuf>,
+
+    /// Structured format for `--output`: `table` (no file written), `csv`,
+    /// or `json`.
+    #[structopt(long, default_value = "table")]
+    format: OutputFormat,
+}
+
+/// Which `wgpu` backend(s) to enumerate adapters from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BackendArg {
+    Vulkan,
+    Dx12,
+    Metal,
+    Gl,
+    All,
+}
+
+impl std::str::FromStr for BackendArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "vulkan" => Ok(BackendArg::Vulkan),
+            "dx12" => Ok(BackendArg::Dx12),
+            "metal" => Ok(BackendArg::Metal),
+            "gl" => Ok(BackendArg::Gl),
+            "all" => Ok(BackendArg::All),
+            other => Err(format!(
+                "unknown backend `{}`, expected one of vulkan, dx12, metal, gl, all",
+                other
+            )),
+        }
+    }
+}
+
+impl BackendArg {
+    fn to_wgpu_backends(self) -> wgpu::Backends {
+        match self {
+            BackendArg::Vulkan => wgpu::Backends::VULKAN,
+            BackendArg::Dx12 => wgpu::Backends::DX12,
+            BackendArg::Metal => wgpu::Backends::METAL,
+            BackendArg::Gl => wgpu::Backends::GL,
+            BackendArg::All => wgpu::Backends::all(),
+        }
+    }
 }
 
 fn get_default_sizes() -> Vec<usize> {
@@ -92,10 +242,55 @@ fn create_tables() -> Vec<Table> {
             "Bandwidth (MB/s)"
         ]);
     }
+
     tables
 }
 
-fn add_measurement(table: &mut Table, iteration: usize, data_size: usize, timings: &[Duration]) {
+/// `with_stats` adds an "Invocations" column, populated only when the
+/// adapter actually supports `PIPELINE_STATISTICS_QUERY` and `--stats` was
+/// requested; otherwise the column is omitted entirely rather than printed
+/// empty.
+fn create_compute_table(with_stats: bool) -> Table {
+    let mut compute_table = Table::new();
+    compute_table.set_format(*format::consts::FORMAT_BOX_CHARS);
+    if with_stats {
+        compute_table.add_row(row![
+            "Iteration",
+            "Datasize (bytes)",
+            "Datasize (MB)",
+            "min Time (ms)",
+            "max (ms)",
+            "avg Time (ms)",
+            "GFLOP/s",
+            "Effective Bandwidth (MB/s)",
+            "Invocations"
+        ]);
+    } else {
+        compute_table.add_row(row![
+            "Iteration",
+            "Datasize (bytes)",
+            "Datasize (MB)",
+            "min Time (ms)",
+            "max (ms)",
+            "avg Time (ms)",
+            "GFLOP/s",
+            "Effective Bandwidth (MB/s)"
+        ]);
+    }
+    compute_table
+}
+
+#[allow(clippy::too_many_arguments)]
+fn add_measurement(
+    table: &mut Table,
+    results: &mut ResultsWriter,
+    adapter: &str,
+    timestamp_period: f32,
+    phase: &str,
+    iteration: usize,
+    data_size: usize,
+    timings: &[Duration],
+) {
     let (min, max, avg) = get_min_max_avg(timings);
     let data_size_mb = data_size as f32 / 1024.0 / 1024.0;
     let bandwidth = data_size_mb / avg * 1000.0;
@@ -108,40 +303,167 @@ fn add_measurement(table: &mut Table, iteration: usize, data_size: usize, timing
         format!("{:.2}", avg),
         format!("{:.2}", bandwidth)
     ]);
+    results.push(MeasurementRecord {
+        adapter: adapter.to_string(),
+        timestamp_period_ns: timestamp_period,
+        phase: phase.to_string(),
+        iteration,
+        data_size,
+        min_ms: min,
+        max_ms: max,
+        avg_ms: avg,
+        bandwidth_mb_s: bandwidth,
+        gflops: None,
+        invocations: None,
+    });
 }
 
-async fn run(config: Configuration) {
-    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-        backends: wgpu::Backends::all(),
-        dx12_shader_compiler: wgpu::Dx12Compiler::Fxc,
+#[allow(clippy::too_many_arguments)]
+fn add_compute_measurement(
+    table: &mut Table,
+    results: &mut ResultsWriter,
+    adapter: &str,
+    timestamp_period: f32,
+    iteration: usize,
+    data_size: usize,
+    ops_per_element: u64,
+    timings: &[Duration],
+    invocations: Option<u64>,
+) {
+    let (min, max, avg) = get_min_max_avg(timings);
+    let data_size_mb = data_size as f32 / 1024.0 / 1024.0;
+    let bandwidth = effective_bandwidth_mb_s(data_size, avg);
+    let element_count = (data_size / mem::size_of::<f32>()).max(1);
+    let gflops = (element_count as f64 * ops_per_element as f64) / (avg as f64 / 1000.0) / 1e9;
+    match invocations {
+        Some(invocations) => table.add_row(row![
+            iteration,
+            data_size,
+            format!("{:.2}", data_size_mb),
+            format!("{:.2}", min),
+            format!("{:.2}", max),
+            format!("{:.2}", avg),
+            format!("{:.2}", gflops),
+            format!("{:.2}", bandwidth),
+            invocations
+        ]),
+        None => table.add_row(row![
+            iteration,
+            data_size,
+            format!("{:.2}", data_size_mb),
+            format!("{:.2}", min),
+            format!("{:.2}", max),
+            format!("{:.2}", avg),
+            format!("{:.2}", gflops),
+            format!("{:.2}", bandwidth)
+        ]),
+    };
+    results.push(MeasurementRecord {
+        adapter: adapter.to_string(),
+        timestamp_period_ns: timestamp_period,
+        phase: "compute".to_string(),
+        iteration,
+        data_size,
+        min_ms: min,
+        max_ms: max,
+        avg_ms: avg,
+        bandwidth_mb_s: bandwidth,
+        gflops: Some(gflops),
+        invocations,
     });
+}
 
-    let adapter = instance
-        .request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::HighPerformance,
-            compatible_surface: None,
-            force_fallback_adapter: false,
-        })
-        .await
-        .unwrap();
-    println!("using adapter: {:?}", adapter);
-
-    let (device, queue) = adapter
-        .request_device(
-            &wgpu::DeviceDescriptor {
-                label: None,
-                features: wgpu::Features::TIMESTAMP_QUERY
-                    | wgpu::Features::PIPELINE_STATISTICS_QUERY,
-                limits: wgpu::Limits::default(),
-            },
-            None,
-        )
-        .await
-        .unwrap();
+async fn run(config: Configuration) {
+    let backends = config.backend.to_wgpu_backends();
+    let adapter_name_filter = config
+        .adapter
+        .clone()
+        .or_else(|| std::env::var("WGPU_ADAPTER_NAME").ok());
+
+    let gpus = WgpuBackend::enumerate(
+        backends,
+        adapter_name_filter.as_deref(),
+        gpu::resolve_power_preference(),
+    )
+    .await;
+    if gpus.is_empty() {
+        panic!(
+            "no adapter found for backend={:?} adapter_name={:?}",
+            config.backend, adapter_name_filter
+        );
+    }
+
+    let mut peak_bandwidths: Vec<(String, f32)> = Vec::new();
+    let mut results = ResultsWriter::new();
+
+    for backend in &gpus {
+        println!("=== Adapter: {} ===", backend.description());
+
+        let support = backend.support();
+        if !support.timestamps {
+            println!(
+                "adapter {} does not support TIMESTAMP_QUERY; GPU-side timings are disabled",
+                backend.name()
+            );
+        }
+        if config.stats && !support.pipeline_statistics {
+            println!(
+                "adapter {} does not support PIPELINE_STATISTICS_QUERY; --stats is disabled for this adapter",
+                backend.name()
+            );
+        }
+
+        let peak_bandwidth = match config.mode {
+            Mode::Copy => run_copy(&config, backend, &mut results).await,
+            Mode::Compute => run_compute(&config, backend, &mut results).await,
+        };
+        peak_bandwidths.push((backend.name().to_string(), peak_bandwidth));
+    }
+
+    if gpus.len() > 1 {
+        print_adapter_comparison(&peak_bandwidths);
+    }
+
+    if let Some(output) = &config.output {
+        results
+            .write(output, config.format)
+            .unwrap_or_else(|err| panic!("failed to write results to {}: {}", output.display(), err));
+    }
+}
+
+fn print_adapter_comparison(peak_bandwidths: &[(String, f32)]) {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_BOX_CHARS);
+    table.add_row(row!["Adapter", "Peak Bandwidth (MB/s)"]);
+    for (name, bandwidth) in peak_bandwidths {
+        table.add_row(row![name, format!("{:.2}", bandwidth)]);
+    }
+    println!("Adapter comparison (peak bandwidth)");
+    table.printstd();
+}
+
+fn bandwidth_mb_s(data_size: usize, avg_ms: f32) -> f32 {
+    let data_size_mb = data_size as f32 / 1024.0 / 1024.0;
+    data_size_mb / avg_ms * 1000.0
+}
+
+/// "Effective" bandwidth for the compute kernel: it both reads `input_buf`
+/// and writes `output_buf`, so the traffic moved is twice `data_size`, unlike
+/// the single-direction copy-mode phases `bandwidth_mb_s` is used for.
+fn effective_bandwidth_mb_s(data_size: usize, avg_ms: f32) -> f32 {
+    2.0 * bandwidth_mb_s(data_size, avg_ms)
+}
 
+async fn run_copy<B: GpuBackend>(
+    config: &Configuration,
+    backend: &B,
+    results: &mut ResultsWriter,
+) -> f32 {
     let mut tables = create_tables();
-    let timestamp_period = queue.get_timestamp_period();
 
+    let has_timestamps = backend.support().timestamps;
+    let timestamp_period = backend.timestamp_scale();
+    let adapter = backend.name();
     let data_sizes = get_default_sizes();
     //let data_sizes = get_power_two_sizes(config.end_power as u32);
 
@@ -149,6 +471,10 @@ async fn run(config: Configuration) {
     let mut pb = ProgressBar::new(data_sizes.len() as u64);
     pb.format("╢▌▌░╟");
 
+    let mut peak_bandwidth = 0.0_f32;
+    let timeout = Duration::from_millis(config.timeout_ms);
+    let phase_names = ["upload", "GPU/GPU transfer", "download"];
+
     for (iteration, data_size) in data_sizes.iter().enumerate() {
         let upload_data = vec![iteration as u8; *data_size];
         let mut download_data = vec![0; *data_size];
@@ -158,23 +484,45 @@ async fn run(config: Configuration) {
         for _ in 1..=config.tries {
             let expected_sum = iteration * data_size;
             let timings = execute_gpu(
-                &device,
-                &queue,
+                backend,
                 expected_sum,
                 &upload_data,
                 &mut download_data,
                 config.verify,
+                timeout,
             )
             .await;
 
-            for it in times.iter_mut().zip(timings.iter()) {
-                let (times, timing) = it;
-                times.push(*timing);
+            for (phase, (times, timing)) in times.iter_mut().zip(timings.iter()).enumerate() {
+                match timing {
+                    Some(timing) => times.push(*timing),
+                    None if phase == 1 && !has_timestamps => {}
+                    None => println!(
+                        "timeout: data size {} stalled during {} phase, skipping measurement",
+                        data_size, phase_names[phase]
+                    ),
+                }
             }
         }
-        for it in tables.iter_mut().zip(times.iter()) {
+        for (phase, it) in tables.iter_mut().zip(times.iter()).enumerate() {
             let (table, times) = it;
-            add_measurement(table, iteration, *data_size, &times[..]);
+            if !times.is_empty() {
+                add_measurement(
+                    table,
+                    results,
+                    adapter,
+                    timestamp_period,
+                    phase_names[phase],
+                    iteration,
+                    *data_size,
+                    &times[..],
+                );
+            }
+        }
+
+        if !times[2].is_empty() {
+            let (_, _, download_avg) = get_min_max_avg(&times[2]);
+            peak_bandwidth = peak_bandwidth.max(bandwidth_mb_s(*data_size, download_avg));
         }
 
         pb.inc();
@@ -188,126 +536,423 @@ async fn run(config: Configuration) {
 
     println!("Download times");
     tables[2].printstd();
+
+    peak_bandwidth
 }
 
-async fn execute_gpu(
-    device: &wgpu::Device,
-    queue: &wgpu::Queue,
+async fn run_compute<B: GpuBackend>(
+    config: &Configuration,
+    backend: &B,
+    results: &mut ResultsWriter,
+) -> f32 {
+    let device = backend.device();
+    let queue = backend.queue();
+    let timestamp_period = backend.timestamp_scale();
+    let adapter = backend.name();
+    let collect_stats = config.stats && backend.support().pipeline_statistics;
+    let mut compute_table = create_compute_table(collect_stats);
+
+    // The shader module, bind-group layout, pipeline layout, and pipeline are
+    // size-independent, so they're built once here rather than per dispatch
+    // (`tries * sizes` times); only the buffers/bind group vary by size.
+    let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("compute fma shader"),
+        source: wgpu::ShaderSource::Wgsl(COMPUTE_SHADER.into()),
+    });
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("compute bind group layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ],
+    });
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("compute pipeline layout"),
+        bind_group_layouts: &[&bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("compute pipeline"),
+        layout: Some(&pipeline_layout),
+        module: &shader_module,
+        entry_point: "main",
+    });
+
+    let data_sizes = get_default_sizes();
+
+    println!("Running {} tests...", data_sizes.len());
+    let mut pb = ProgressBar::new(data_sizes.len() as u64);
+    pb.format("╢▌▌░╟");
+
+    let ops_per_element = u64::from(FMA_ITERATIONS) * 2;
+    let mut peak_bandwidth = 0.0_f32;
+    let timeout = Duration::from_millis(config.timeout_ms);
+    let has_timestamps = backend.support().timestamps;
+
+    for (iteration, data_size) in data_sizes.iter().enumerate() {
+        let element_count = (data_size / mem::size_of::<f32>()).max(1);
+
+        let mut times = Vec::with_capacity(config.tries as usize);
+        let mut invocations = None;
+        for _ in 1..=config.tries {
+            let result = execute_gpu_compute(
+                device,
+                queue,
+                &pipeline,
+                &bind_group_layout,
+                element_count,
+                timestamp_period,
+                config.verify,
+                timeout,
+                collect_stats,
+                has_timestamps,
+            )
+            .await;
+            match result {
+                Some(result) => {
+                    // `result.time` is `None` when the adapter lacks
+                    // TIMESTAMP_QUERY, same as the copy-mode GPU/GPU phase:
+                    // the dispatch still ran, it's just left unmeasured
+                    // rather than recorded as a stall.
+                    if let Some(time) = result.time {
+                        times.push(time);
+                    }
+                    invocations = invocations.or(result.invocations);
+                }
+                None => println!(
+                    "timeout: data size {} stalled during compute dispatch, skipping measurement",
+                    data_size
+                ),
+            }
+        }
+
+        if !times.is_empty() {
+            add_compute_measurement(
+                &mut compute_table,
+                results,
+                adapter,
+                timestamp_period,
+                iteration,
+                *data_size,
+                ops_per_element,
+                &times,
+                invocations,
+            );
+
+            let (_, _, avg) = get_min_max_avg(&times);
+            peak_bandwidth = peak_bandwidth.max(effective_bandwidth_mb_s(*data_size, avg));
+        }
+
+        pb.inc();
+    }
+    pb.finish_print("Finished test");
+    println!("Compute throughput times");
+    compute_table.printstd();
+
+    peak_bandwidth
+}
+
+async fn execute_gpu<B: GpuBackend>(
+    backend: &B,
     expected_sum: usize,
     host_data_upload: &[u8],
     host_data_download: &mut [u8],
     verify: bool,
-) -> Vec<Duration> {
-    let slice_size = host_data_upload.len() * std::mem::size_of::<u8>();
-    let size = slice_size as wgpu::BufferAddress;
-
-    let upload_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-        label: None,
-        size,
-        usage: wgpu::BufferUsages::MAP_WRITE | wgpu::BufferUsages::COPY_SRC,
+    timeout: Duration,
+) -> Vec<Option<Duration>> {
+    let has_timestamps = backend.support().timestamps;
+    let size = host_data_upload.len() as u64;
+
+    let upload_buffer = backend.create_upload_buffer(size);
+    let download_buffer = backend.create_download_buffer(size);
+
+    let upload_time = match backend.write(&upload_buffer, host_data_upload, timeout).await {
+        Some(time) => time,
+        None => return vec![None, None, None],
+    };
+
+    // GPU/GPU transfer. Timing requires TIMESTAMP_QUERY; if the adapter
+    // doesn't support it, the copy still runs but this phase is left
+    // unmeasured (`None`), same as a stalled/timed-out copy, so no bogus
+    // zero-time row is ever recorded for it.
+    let gpu_gpu_time = match backend.copy(&upload_buffer, &download_buffer, size, timeout).await {
+        Some(time) => Some(time),
+        None if !has_timestamps => None,
+        None => return vec![Some(upload_time), None, None],
+    };
+
+    let download_time = backend
+        .read_back(&download_buffer, host_data_download, timeout)
+        .await
+        .map(|time| {
+            if verify {
+                let mut total: usize = 0;
+                for item in host_data_download.iter() {
+                    total += *item as usize;
+                }
+                assert_eq!(expected_sum, total);
+            }
+            time
+        });
+
+    vec![Some(upload_time), gpu_gpu_time, download_time]
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn execute_gpu_compute(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    pipeline: &wgpu::ComputePipeline,
+    bind_group_layout: &wgpu::BindGroupLayout,
+    element_count: usize,
+    timestamp_period: f32,
+    verify: bool,
+    timeout: Duration,
+    collect_stats: bool,
+    has_timestamps: bool,
+) -> Option<ComputeResult> {
+    let buffer_size = (element_count * mem::size_of::<f32>()) as wgpu::BufferAddress;
+
+    let input_data = vec![1.0_f32; element_count];
+
+    let input_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("compute input buffer"),
+        size: buffer_size,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    queue.write_buffer(&input_buffer, 0, bytemuck::cast_slice(&input_data));
+
+    let output_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("compute output buffer"),
+        size: buffer_size,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
         mapped_at_creation: false,
     });
 
-    let download_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-        size,
+    let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("compute readback buffer"),
+        size: buffer_size,
         usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
-        label: None,
         mapped_at_creation: false,
     });
-    device.poll(wgpu::Maintain::Wait);
 
-    let upload_time = {
-        let start = Instant::now();
-        let buffer_slice = upload_buffer.slice(..);
-        let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
-        buffer_slice.map_async(wgpu::MapMode::Write, move |v| sender.send(v).unwrap());
-        device.poll(wgpu::Maintain::Wait);
-
-        if let Some(Ok(())) = receiver.receive().await {
-            let mut data = buffer_slice.get_mapped_range_mut();
-            data.copy_from_slice(host_data_upload);
-            device.poll(wgpu::Maintain::Wait);
-            drop(data);
-            upload_buffer.unmap();
-        } else {
-            println!("oops");
-        }
-        device.poll(wgpu::Maintain::Wait);
-        start.elapsed()
+    let params = ComputeParams {
+        iterations: FMA_ITERATIONS,
+        _padding: [0; 3],
     };
-
-    let timing_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-        label: Some("timing buffer"),
-        size: 2 * mem::size_of::<u64>() as wgpu::BufferAddress,
-        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+    let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("compute params buffer"),
+        size: mem::size_of::<ComputeParams>() as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         mapped_at_creation: false,
     });
-    let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
-        label: None,
-        count: 2,
-        ty: wgpu::QueryType::Timestamp,
+    queue.write_buffer(&params_buffer, 0, bytemuck::bytes_of(&params));
+
+    // Timestamp query/buffer are only created when the adapter advertises
+    // TIMESTAMP_QUERY; requesting them unconditionally would trip wgpu's
+    // validation (and the default uncaptured-error handler would panic) on
+    // adapters that lack the feature, since `WgpuBackend` no longer force-
+    // enables it (gpu.rs).
+    let timing_buffer = has_timestamps.then(|| {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("compute timing buffer"),
+            size: 2 * mem::size_of::<u64>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        })
+    });
+    let query_set = has_timestamps.then(|| {
+        device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: None,
+            count: 2,
+            ty: wgpu::QueryType::Timestamp,
+        })
     });
-    // GPU/GPU transfer
-    let gpu_gpu_time = {
-        let start = Instant::now();
-        let mut encoder =
-            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-        encoder.write_timestamp(&query_set, 0);
-        encoder.copy_buffer_to_buffer(&upload_buffer, 0, &download_buffer, 0, size);
-        encoder.write_timestamp(&query_set, 1);
-        encoder.resolve_query_set(&query_set, 0..2, &timing_buffer, 0);
-        queue.submit(Some(encoder.finish()));
-        device.poll(wgpu::Maintain::Wait);
 
-        let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
-        let _ = timing_buffer
-            .slice(..)
-            .map_async(wgpu::MapMode::Read, move |v| sender.send(v).unwrap());
-        // Wait for device to be done rendering mipmaps
-        device.poll(wgpu::Maintain::Wait);
-        if let Some(Ok(())) = receiver.receive().await {
-            let view = timing_buffer.slice(..).get_mapped_range();
-            // Convert the raw data into a useful structure
-            let data: &TimestampData = bytemuck::from_bytes(&*view);
-            //println!("sdf: {} us", (data.end - data.start)/1000);
-            Duration::from_nanos(data.end - data.start)
-            //start.elapsed()
-        } else {
-            Duration::default()
-        }
-    };
+    let stats_query_set = collect_stats.then(|| {
+        device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("compute pipeline statistics query set"),
+            count: 1,
+            ty: wgpu::QueryType::PipelineStatistics(
+                wgpu::PipelineStatisticsTypes::COMPUTE_SHADER_INVOCATIONS,
+            ),
+        })
+    });
+    let stats_buffer = collect_stats.then(|| {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("compute pipeline statistics buffer"),
+            size: mem::size_of::<PipelineStatisticsData>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        })
+    });
+
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("compute bind group"),
+        layout: bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: input_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: output_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: params_buffer.as_entire_binding(),
+            },
+        ],
+    });
 
-    let download_time = {
-        let start = Instant::now();
-        let mut end_time = Duration::from_secs(0);
+    let workgroup_count = ((element_count as u32) + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
+    let (workgroups_x, workgroups_y) = workgroup_grid(workgroup_count);
 
-        let buffer_slice = download_buffer.slice(..);
+    let mut encoder =
+        device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    if let Some(query_set) = &query_set {
+        encoder.write_timestamp(query_set, 0);
+    }
+    {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        if let Some(stats_query_set) = &stats_query_set {
+            pass.begin_pipeline_statistics_query(stats_query_set, 0);
+        }
+        pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
+        if stats_query_set.is_some() {
+            pass.end_pipeline_statistics_query();
+        }
+    }
+    if let (Some(query_set), Some(timing_buffer)) = (&query_set, &timing_buffer) {
+        encoder.write_timestamp(query_set, 1);
+        encoder.resolve_query_set(query_set, 0..2, timing_buffer, 0);
+    }
+    if let (Some(stats_query_set), Some(stats_buffer)) = (&stats_query_set, &stats_buffer) {
+        encoder.resolve_query_set(stats_query_set, 0..1, stats_buffer, 0);
+    }
+    if verify {
+        encoder.copy_buffer_to_buffer(&output_buffer, 0, &readback_buffer, 0, buffer_size);
+    }
+    queue.submit(Some(encoder.finish()));
+
+    // `None` when the adapter doesn't support TIMESTAMP_QUERY: the dispatch
+    // still ran above, it's just left unmeasured (mirrors `WgpuBackend::copy`
+    // in gpu.rs). A stalled/failed map of an *existing* timing buffer aborts
+    // the whole measurement via `?` below, as does a stalled `--verify`
+    // readback further down.
+    let gpu_time: Option<Option<Duration>> = match &timing_buffer {
+        Some(timing_buffer) => {
+            let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
+            let _ = timing_buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |v| sender.send(v).unwrap());
+
+            match gpu::poll_until_mapped(device, receiver, timeout).await {
+                Some(Ok(())) => {
+                    let view = timing_buffer.slice(..).get_mapped_range();
+                    let data: &TimestampData = bytemuck::from_bytes(&*view);
+                    let elapsed_ns = (data.end - data.start) as f64 * timestamp_period as f64;
+                    Some(Some(Duration::from_nanos(elapsed_ns as u64)))
+                }
+                Some(Err(err)) => {
+                    eprintln!("failed to map compute timing buffer: {}", err);
+                    None
+                }
+                None => None,
+            }
+        }
+        None => Some(None),
+    };
+    let gpu_time = gpu_time?;
+
+    let invocations = match &stats_buffer {
+        Some(stats_buffer) => {
+            let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
+            stats_buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |v| sender.send(v).unwrap());
+
+            match gpu::poll_until_mapped(device, receiver, timeout).await {
+                Some(Ok(())) => {
+                    let view = stats_buffer.slice(..).get_mapped_range();
+                    let data: &PipelineStatisticsData = bytemuck::from_bytes(&*view);
+                    Some(data.compute_shader_invocations)
+                }
+                Some(Err(err)) => {
+                    eprintln!("failed to map pipeline-statistics buffer: {}", err);
+                    None
+                }
+                None => None,
+            }
+        }
+        None => None,
+    };
 
+    if verify {
+        let buffer_slice = readback_buffer.slice(..);
         let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
         buffer_slice.map_async(wgpu::MapMode::Read, move |v| sender.send(v).unwrap());
-        device.poll(wgpu::Maintain::Wait);
 
-        if let Some(Ok(())) = receiver.receive().await {
-            let data = buffer_slice.get_mapped_range();
-            host_data_download.copy_from_slice(&data);
-            drop(data);
-            download_buffer.unmap();
-            end_time = start.elapsed();
+        match gpu::poll_until_mapped(device, receiver, timeout).await {
+            Some(Ok(())) => {
+                let view = buffer_slice.get_mapped_range();
+                let actual: &[f32] = bytemuck::cast_slice(&view);
 
-            if verify {
-                let mut total: usize = 0;
-                for item in host_data_download {
-                    total += *item as usize;
+                let mut expected = 1.0_f32;
+                for _ in 0..FMA_ITERATIONS {
+                    expected = expected * 1.0000001 + 0.0000001;
+                }
+
+                for value in actual {
+                    assert!(
+                        (value - expected).abs() < expected * 1e-3,
+                        "compute verify mismatch: expected {}, got {}",
+                        expected,
+                        value
+                    );
                 }
-                assert_eq!(expected_sum, total);
             }
-        } else {
-            println!("oops");
+            Some(Err(err)) => eprintln!("failed to map verify readback buffer: {}", err),
+            None => return None,
         }
-        device.poll(wgpu::Maintain::Wait);
-        end_time
-    };
-    vec![upload_time, gpu_gpu_time, download_time]
+    }
+
+    Some(ComputeResult {
+        time: gpu_time,
+        invocations,
+    })
 }
 
 pub fn main() {
@@ -0,0 +1,128 @@
+//! Structured result recording for `--output`: besides the live
+//! `ProgressBar` and `prettytable` view, each measurement row can also be
+//! serialized to CSV or JSON so bandwidth-vs-size curves can be plotted and
+//! results diffed across driver versions or machines. CSV rows stay
+//! self-contained (adapter/`timestamp_period_ns` repeated per row) since CSV
+//! has no header/object split; JSON additionally wraps the records in a top-level
+//! object with an adapter/`timestamp_period_ns` metadata header, since a
+//! single run can sweep more than one adapter.
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+/// Which structured format to additionally write to `--output`, alongside
+/// the human-readable tables. `Table` means no file is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Table,
+    Csv,
+    Json,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(OutputFormat::Table),
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            other => Err(format!(
+                "unknown format `{}`, expected one of table, csv, json",
+                other
+            )),
+        }
+    }
+}
+
+/// One `add_measurement`/`add_compute_measurement` row. Carries the adapter
+/// name and `timestamp_period_ns` on every record (in both CSV and JSON
+/// output) so CSV rows stay self-contained and comparable across
+/// runs/machines without needing to join against a side file.
+#[derive(Serialize)]
+pub struct MeasurementRecord {
+    pub adapter: String,
+    pub timestamp_period_ns: f32,
+    pub phase: String,
+    pub iteration: usize,
+    pub data_size: usize,
+    pub min_ms: f32,
+    pub max_ms: f32,
+    pub avg_ms: f32,
+    pub bandwidth_mb_s: f32,
+    pub gflops: Option<f64>,
+    pub invocations: Option<u64>,
+}
+
+/// Metadata describing one adapter a run swept: its name and the
+/// `timestamp_period_ns` used to scale its raw GPU timestamp deltas, so runs
+/// from different machines stay comparable. The JSON records still repeat
+/// both fields on every row as well.
+#[derive(Serialize)]
+struct AdapterMetadata {
+    adapter: String,
+    timestamp_period_ns: f32,
+}
+
+/// Top-level shape written for `--format json`: a metadata header describing
+/// every adapter the run swept, plus the flat list of measurement rows.
+#[derive(Serialize)]
+struct JsonOutput<'a> {
+    adapters: Vec<AdapterMetadata>,
+    records: &'a [MeasurementRecord],
+}
+
+/// Accumulates `MeasurementRecord`s across every adapter/mode run and writes
+/// them to `--output` in the requested `--format` once the run finishes.
+/// Purely additive: the live progress bar and tables are unaffected.
+#[derive(Default)]
+pub struct ResultsWriter {
+    records: Vec<MeasurementRecord>,
+}
+
+impl ResultsWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, record: MeasurementRecord) {
+        self.records.push(record);
+    }
+
+    pub fn write(&self, path: &Path, format: OutputFormat) -> io::Result<()> {
+        match format {
+            OutputFormat::Table => Ok(()),
+            OutputFormat::Csv => {
+                let file = File::create(path)?;
+                let mut writer = csv::Writer::from_writer(file);
+                for record in &self.records {
+                    writer
+                        .serialize(record)
+                        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+                }
+                writer.flush()
+            }
+            OutputFormat::Json => {
+                let file = File::create(path)?;
+                let mut adapters: Vec<AdapterMetadata> = Vec::new();
+                for record in &self.records {
+                    if !adapters.iter().any(|a| a.adapter == record.adapter) {
+                        adapters.push(AdapterMetadata {
+                            adapter: record.adapter.clone(),
+                            timestamp_period_ns: record.timestamp_period_ns,
+                        });
+                    }
+                }
+                let output = JsonOutput {
+                    adapters,
+                    records: &self.records,
+                };
+                serde_json::to_writer_pretty(file, &output)
+                    .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+            }
+        }
+    }
+}